@@ -2,6 +2,7 @@ use core::ops::Range;
 use std::str;
 use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
 
+pub mod compression;
 pub mod response;
 pub mod status;
 
@@ -11,10 +12,25 @@ mod test;
 const METHODS_WITHOUT_BODY: &[&[u8]] =
     &[b"GET", b"OPTIONS", b"HEAD", b"DELETE", b"CONNECT", b"TRACE"];
 
+/// Headers that are meaningful only for this hop and must not be relayed to the other side of the
+/// proxy, per RFC 7230 §6.1: `Keep-Alive` and any `Proxy-*` header are always stripped, while
+/// `Connection`/`Upgrade` are handled by the caller (kept verbatim only while relaying an actual
+/// upgrade handshake, stripped otherwise). `Transfer-Encoding` is deliberately not listed here:
+/// this proxy streams bodies as raw bytes rather than re-framing them, so the header has to stay
+/// put to describe the bytes that are actually on the wire.
+pub fn is_hop_by_hop_header(name: &[u8]) -> bool {
+    const HOP_BY_HOP: &[&[u8]] = &[b"Keep-Alive", b"TE", b"Trailer"];
+    HOP_BY_HOP.iter().any(|&h| name.eq_ignore_ascii_case(h))
+        || name.len() >= 6 && name[..6].eq_ignore_ascii_case(b"Proxy-")
+}
+
 struct HeaderSummary {
     content_length: Option<u64>,
     transfer_encoding_is_chunked: bool,
     connection_is_close: bool,
+    connection_has_upgrade: bool,
+    has_upgrade_header: bool,
+    expects_continue: bool,
 }
 impl HeaderSummary {
     fn new() -> Self {
@@ -22,6 +38,9 @@ impl HeaderSummary {
             content_length: None,
             transfer_encoding_is_chunked: false,
             connection_is_close: false,
+            connection_has_upgrade: false,
+            has_upgrade_header: false,
+            expects_continue: false,
         }
     }
 
@@ -33,11 +52,30 @@ impl HeaderSummary {
         } else if name.eq_ignore_ascii_case(b"Transfer-Encoding") {
             self.transfer_encoding_is_chunked = value.eq_ignore_ascii_case(b"chunked");
         } else if name.eq_ignore_ascii_case(b"Connection") {
-            self.connection_is_close = value.eq_ignore_ascii_case(b"close");
+            self.connection_is_close = false;
+            self.connection_has_upgrade = false;
+            for token in value.split(|&b| b == b',') {
+                let token = token.trim_ascii();
+                if token.eq_ignore_ascii_case(b"close") {
+                    self.connection_is_close = true;
+                } else if token.eq_ignore_ascii_case(b"upgrade") {
+                    self.connection_has_upgrade = true;
+                }
+            }
+        } else if name.eq_ignore_ascii_case(b"Upgrade") {
+            self.has_upgrade_header = true;
+        } else if name.eq_ignore_ascii_case(b"Expect") {
+            self.expects_continue = value.eq_ignore_ascii_case(b"100-continue");
         }
         Ok(())
     }
 
+    /// true when the message announced a protocol upgrade (`Connection: upgrade` together with
+    /// an `Upgrade` header), as opposed to a regular keep-alive/close connection.
+    fn is_upgrade(&self) -> bool {
+        self.connection_has_upgrade && self.has_upgrade_header
+    }
+
     fn request_length(&self, method: &[u8]) -> Option<u64> {
         if self.transfer_encoding_is_chunked {
             return None;
@@ -214,6 +252,22 @@ impl<'t, R: BytePeekRead> Reader<'t, R> {
             HeaderKind::Response { status_code } => self.summary.response_length(status_code),
         }
     }
+
+    /// true when the message carries `Connection: upgrade` and an `Upgrade` header, meaning the
+    /// caller should switch to opaque byte forwarding once the response confirms the upgrade
+    /// (status 101).
+    pub fn is_upgrade(&self) -> bool {
+        self.summary.is_upgrade()
+    }
+
+    /// true when the request carries `Expect: 100-continue`. The header itself isn't hop-by-hop
+    /// so it already reaches the backend unmodified, which relays its own interim `100 Continue`
+    /// back to the client (handled generically alongside other 1xx responses); this accessor is
+    /// for callers that need to know the expectation without re-parsing headers themselves.
+    pub fn expects_continue(&self) -> bool {
+        self.summary.expects_continue
+    }
+
 }
 
 pub struct Writer(Vec<u8>);
@@ -276,7 +330,10 @@ impl Writer {
     }
 
     pub fn content(self, content: String) -> Vec<u8> {
-        let mut content = content.into_bytes();
+        self.content_bytes(content.into_bytes())
+    }
+
+    pub fn content_bytes(self, mut content: Vec<u8>) -> Vec<u8> {
         let mut ret = self.content_length(content.len());
         ret.append(&mut content);
         ret
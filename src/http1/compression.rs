@@ -0,0 +1,205 @@
+//! `Accept-Encoding` negotiation and body compression for responses this proxy generates or
+//! buffers in full: locally-built error/redirect pages, and proxied responses small enough to fit
+//! under [`Config::max_body`] (see `copy_response_header` in `main.rs`). Larger proxied bodies
+//! keep streaming straight through uncompressed, since compressing them would mean buffering
+//! arbitrarily large backend responses just to maybe shrink them.
+
+use std::io::Write;
+
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::Compression as Level;
+
+/// A codec this build knows how to produce, ordered most- to least-preferred when a client offers
+/// several with equal `q`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    Gzip,
+    Deflate,
+}
+impl Codec {
+    /// The `Content-Encoding`/`Accept-Encoding` token for this codec.
+    pub fn token(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+}
+
+/// Codecs this build can actually produce. `br`/`zstd` are valid `Accept-Encoding` tokens that
+/// [`negotiate`] simply never picks, since this build has no encoder for them yet; adding one is a
+/// matter of extending this list and [`compress`].
+const SUPPORTED: &[Codec] = &[Codec::Gzip, Codec::Deflate];
+
+/// Body-size bounds a response has to fall within before compression is worth attempting at all;
+/// see [`is_compressible_content_type`] for the (currently non-configurable) content-type check.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub enabled: bool,
+    /// bodies smaller than this many bytes aren't compressed: the gzip/deflate framing overhead
+    /// can make a tiny body larger, not smaller.
+    pub min_size: usize,
+    /// proxied response bodies larger than this many bytes are forwarded uncompressed rather than
+    /// buffered in full: compression needs the whole body in memory up front, which isn't worth
+    /// doing for an upstream response of unbounded size.
+    pub max_body: usize,
+}
+
+/// One `Accept-Encoding` entry: a codec token plus its `q` weight.
+struct Preference<'t> {
+    token: &'t str,
+    q: f32,
+}
+
+/// Parse an `Accept-Encoding` header value into preferences, ordered highest-`q` first (ties keep
+/// the header's original order, since `sort_by` is stable).
+fn parse_accept_encoding(value: &[u8]) -> Vec<Preference<'_>> {
+    let Ok(value) = std::str::from_utf8(value) else {
+        return Vec::new();
+    };
+
+    let mut prefs: Vec<Preference> = value
+        .split(',')
+        .filter_map(|entry| {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                return None;
+            }
+            let (token, params) = entry.split_once(';').unwrap_or((entry, ""));
+            let q = params
+                .split(';')
+                .map(str::trim)
+                .find_map(|p| p.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some(Preference {
+                token: token.trim(),
+                q,
+            })
+        })
+        .collect();
+
+    prefs.sort_by(|a, b| b.q.total_cmp(&a.q));
+    prefs
+}
+
+/// Pick the best codec this build supports for `accept_encoding`, honoring `q=0` exclusions and a
+/// `*` wildcard. `None` means identity: either no `Accept-Encoding` was sent, nothing offered is
+/// supported, or everything this build supports was explicitly excluded.
+pub fn negotiate(accept_encoding: Option<&[u8]>) -> Option<Codec> {
+    let prefs = parse_accept_encoding(accept_encoding?);
+
+    let weight_of = |token: &str| {
+        prefs
+            .iter()
+            .find(|p| p.token.eq_ignore_ascii_case(token))
+            .map(|p| p.q)
+    };
+    let wildcard_weight = weight_of("*").unwrap_or(0.0);
+
+    SUPPORTED
+        .iter()
+        .copied()
+        .map(|codec| (codec, weight_of(codec.token()).unwrap_or(wildcard_weight)))
+        .filter(|&(_, weight)| weight > 0.0)
+        .fold(None, |best: Option<(Codec, f32)>, (codec, weight)| match best {
+            Some((_, best_weight)) if best_weight >= weight => best,
+            _ => Some((codec, weight)),
+        })
+        .map(|(codec, _)| codec)
+}
+
+/// Compress `body` with `codec`, at a middling level: these are small generated/buffered bodies,
+/// not a bulk transfer worth spending much CPU to shrink further.
+pub fn compress(codec: Codec, body: &[u8]) -> std::io::Result<Vec<u8>> {
+    match codec {
+        Codec::Gzip => {
+            let mut enc = GzEncoder::new(Vec::new(), Level::default());
+            enc.write_all(body)?;
+            enc.finish()
+        }
+        Codec::Deflate => {
+            let mut enc = DeflateEncoder::new(Vec::new(), Level::default());
+            enc.write_all(body)?;
+            enc.finish()
+        }
+    }
+}
+
+/// Content types eligible for compression: text-ish formats, checked against the response's
+/// `Content-Type` (parameters like `; charset=utf-8` ignored). Already-compressed formats
+/// (images, video, archives, fonts) are deliberately left off this list — compressing them again
+/// wastes CPU for no size benefit.
+pub fn is_compressible_content_type(content_type: &str) -> bool {
+    const ALLOWED_PREFIXES: &[&str] = &[
+        "text/",
+        "application/json",
+        "application/javascript",
+        "application/xml",
+        "application/xhtml+xml",
+        "image/svg+xml",
+    ];
+
+    let content_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or(content_type)
+        .trim();
+
+    ALLOWED_PREFIXES
+        .iter()
+        .any(|prefix| content_type.eq_ignore_ascii_case(prefix) || starts_with_ignore_case(content_type, prefix))
+}
+
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.len() >= prefix.len() && s.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_header_means_identity() {
+        assert_eq!(negotiate(None), None);
+    }
+
+    #[test]
+    fn higher_q_codec_wins_over_supported_order() {
+        // the client's q weights decide; SUPPORTED's own order (gzip, then deflate) only breaks
+        // ties between equal weights.
+        assert_eq!(
+            negotiate(Some(b"deflate;q=1.0, gzip;q=0.1")),
+            Some(Codec::Deflate)
+        );
+    }
+
+    #[test]
+    fn ties_prefer_gzip_over_deflate() {
+        assert_eq!(
+            negotiate(Some(b"deflate;q=0.5, gzip;q=0.5")),
+            Some(Codec::Gzip)
+        );
+    }
+
+    #[test]
+    fn q_zero_excludes_a_codec() {
+        assert_eq!(negotiate(Some(b"gzip;q=0, deflate")), Some(Codec::Deflate));
+    }
+
+    #[test]
+    fn wildcard_is_used_as_fallback_weight() {
+        assert_eq!(negotiate(Some(b"*;q=0.3")), Some(Codec::Gzip));
+    }
+
+    #[test]
+    fn wildcard_q_zero_excludes_unlisted_codecs() {
+        assert_eq!(negotiate(Some(b"gzip, *;q=0")), Some(Codec::Gzip));
+        assert_eq!(negotiate(Some(b"*;q=0")), None);
+    }
+
+    #[test]
+    fn unsupported_codecs_are_ignored() {
+        assert_eq!(negotiate(Some(b"br;q=1.0")), None);
+    }
+}
@@ -1,30 +1,100 @@
-use super::{status, Writer};
+use super::{compression, status, Writer};
 
-pub fn status(status: &[u8]) -> Vec<u8> {
-    plain(status, status)
+pub fn status(status: &[u8], accept_encoding: Option<&[u8]>, compression: &compression::Config) -> Vec<u8> {
+    plain(status, status, accept_encoding, compression)
 }
 
-pub fn plain(status: &[u8], message: &[u8]) -> Vec<u8> {
-    let mut w = Writer::new();
+pub fn plain(
+    status: &[u8],
+    message: &[u8],
+    accept_encoding: Option<&[u8]>,
+    compression: &compression::Config,
+) -> Vec<u8> {
+    let mut body = message.to_vec();
+    body.push(b'\n');
 
-    w.status(status);
+    body_response(status, "text/plain", body, accept_encoding, compression, &[])
+}
 
-    w.header("Content-Type", "text/plain");
+/// Build a response from a fully-buffered `body`, compressing it for `accept_encoding` when
+/// `compression` allows it (enabled, `body` at least `min_size`, and `content_type` passes
+/// [`compression::is_compressible_content_type`]), and always advertising
+/// `Vary: Accept-Encoding` so a cache sitting in front of kingress doesn't serve the wrong variant
+/// to a client with different capabilities. `extra_headers` (e.g. `Location`) land between
+/// `Content-Type` and the compression headers.
+fn body_response(
+    status: &[u8],
+    content_type: &str,
+    mut body: Vec<u8>,
+    accept_encoding: Option<&[u8]>,
+    compression: &compression::Config,
+    extra_headers: &[(&str, &str)],
+) -> Vec<u8> {
+    let codec = (compression.enabled
+        && body.len() >= compression.min_size
+        && compression::is_compressible_content_type(content_type))
+    .then(|| compression::negotiate(accept_encoding))
+    .flatten();
 
-    let mut w = w.content_length(message.len() + 1);
+    let mut w = Writer::new();
+    w.status(status);
+    w.header("Content-Type", content_type);
+    for (name, value) in extra_headers {
+        w.header(name, value);
+    }
+    w.header("Vary", "Accept-Encoding");
 
-    w.extend_from_slice(message);
-    w.push(b'\n');
-    w
+    if let Some(codec) = codec {
+        if let Ok(compressed) = compression::compress(codec, &body) {
+            w.header("Content-Encoding", codec.token());
+            body = compressed;
+        }
+    }
+
+    w.content_bytes(body)
 }
 
-pub fn redirect(target_url: &str) -> Vec<u8> {
+/// Build a CORS preflight response: `204 No Content` with the allow-methods/headers reflected
+/// and the origin/credentials/max-age headers set as configured.
+pub fn cors_preflight(
+    origin: &str,
+    request_method: &str,
+    request_headers: Option<&str>,
+    allow_credentials: bool,
+    max_age_secs: Option<u64>,
+) -> Vec<u8> {
     let mut w = Writer::new();
 
-    w.status(status::MOVED_PERMANENTLY);
-    w.append_str("HTTP/1.1 301 Moved Permanently\r\n");
-    w.header("Location", target_url);
-    w.header("Content-Type", "text/html");
+    w.status(status::NO_CONTENT);
+    w.header("Access-Control-Allow-Origin", origin);
+    w.header("Vary", "Origin");
+    w.header("Access-Control-Allow-Methods", request_method);
+    if let Some(request_headers) = request_headers {
+        w.header("Access-Control-Allow-Headers", request_headers);
+    }
+    if allow_credentials {
+        w.header("Access-Control-Allow-Credentials", "true");
+    }
+    if let Some(max_age_secs) = max_age_secs {
+        w.header("Access-Control-Max-Age", &max_age_secs.to_string());
+    }
+
+    w.content_length(0)
+}
+
+pub fn redirect(
+    target_url: &str,
+    accept_encoding: Option<&[u8]>,
+    compression: &compression::Config,
+) -> Vec<u8> {
+    let body = format!("<a href=\"{target_url}\">Moved Permanently</a>.\n").into_bytes();
 
-    w.content(format!("<a href=\"{target_url}\">Moved Permanently</a>.\n"))
+    body_response(
+        status::MOVED_PERMANENTLY,
+        "text/html",
+        body,
+        accept_encoding,
+        compression,
+        &[("Location", target_url)],
+    )
 }
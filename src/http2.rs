@@ -0,0 +1,124 @@
+//! HTTP/2 proxying driven by the `h2` crate.
+//!
+//! Unlike the `http1` path, a single TLS (or cleartext h2c) connection here carries many
+//! concurrent logical requests, so we cannot reuse the "one endpoint per connection" model used
+//! for the opaque `*`-only passthrough the binary used before this module existed. Each client
+//! stream is routed independently (the caller supplies the routing and backend-dialing
+//! callbacks, mirroring how `main.rs` owns `ctx()` and `Backend::connect` for the http1 path) and
+//! proxied to a native h2 backend stream-for-stream.
+//!
+//! There's no HTTP/1.1 translation for endpoints that aren't `http2`-capable: the caller rejects
+//! those before routing here, since this module can only speak h2 to backends.
+
+use bytes::Bytes;
+use h2::{server, RecvStream};
+use http::{Request, Response};
+use log::debug;
+use std::future::Future;
+use std::net::SocketAddr;
+
+use crate::Endpoint;
+
+/// What to do with a single h2 stream, decided by the caller from the request's
+/// `:path`/`:authority`.
+pub enum Route {
+    /// Proxy to this `http2`-capable endpoint over a native h2 backend connection.
+    Endpoint(Endpoint),
+    /// No host/endpoint matched (or the matched endpoint isn't `http2`-capable); answer directly
+    /// with this status.
+    Reject(u16),
+}
+
+/// Drive a single h2 server connection, dispatching each stream through `route` and `connect`.
+///
+/// `route` maps a request's `:authority`/`:path` to an `Endpoint` (or a rejection), exactly like
+/// `HostConfig::endpoint_for` does for the http1 path. `connect` dials (or reuses) the backend's
+/// `SendRequest`, returning `None` on a connect failure, which is reported to the client as a 502.
+pub async fn serve<IO, RouteFn, ConnectFn, ConnectFut>(
+    io: IO,
+    remote: SocketAddr,
+    route: RouteFn,
+    connect: ConnectFn,
+) -> h2::Result<()>
+where
+    IO: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+    RouteFn: Fn(&Request<RecvStream>) -> Route + Clone + Send + 'static,
+    ConnectFn: Fn(Endpoint) -> ConnectFut + Clone + Send + 'static,
+    ConnectFut: Future<Output = Option<h2::client::SendRequest<Bytes>>> + Send + 'static,
+{
+    let mut conn = server::handshake(io).await?;
+
+    while let Some(result) = conn.accept().await {
+        let (req, respond) = result?;
+        let route = route.clone();
+        let connect = connect.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_stream(req, respond, remote, route, connect).await {
+                debug!("{remote}: h2 stream failed: {e}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+async fn handle_stream<RouteFn, ConnectFn, ConnectFut>(
+    req: Request<RecvStream>,
+    mut respond: server::SendResponse<Bytes>,
+    remote: SocketAddr,
+    route: RouteFn,
+    connect: ConnectFn,
+) -> h2::Result<()>
+where
+    RouteFn: Fn(&Request<RecvStream>) -> Route,
+    ConnectFn: Fn(Endpoint) -> ConnectFut,
+    ConnectFut: Future<Output = Option<h2::client::SendRequest<Bytes>>>,
+{
+    let endpoint = match route(&req) {
+        Route::Endpoint(ep) => ep,
+        Route::Reject(status) => {
+            let resp = Response::builder().status(status).body(()).unwrap();
+            respond.send_response(resp, true)?;
+            return Ok(());
+        }
+    };
+
+    debug!("{remote}: h2 stream mapped to {endpoint}");
+
+    let Some(mut backend_send) = connect(endpoint).await else {
+        // `route` only hands us `http2`-capable endpoints, so reaching here means dialing (or
+        // reusing) the backend's h2 connection itself failed.
+        let resp = Response::builder().status(502).body(()).unwrap();
+        respond.send_response(resp, true)?;
+        return Ok(());
+    };
+
+    let (head, mut body) = req.into_parts();
+    let backend_req = Request::from_parts(head, ());
+    let (backend_resp, mut backend_body_send) =
+        backend_send.send_request(backend_req, false)?;
+
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        let len = chunk.len();
+        backend_body_send.send_data(chunk, false)?;
+        body.flow_control().release_capacity(len)?;
+    }
+    backend_body_send.send_data(Bytes::new(), true)?;
+
+    let backend_resp = backend_resp.await?;
+    let (head, mut backend_body) = backend_resp.into_parts();
+
+    let resp = Response::from_parts(head, ());
+    let mut client_send = respond.send_response(resp, false)?;
+
+    while let Some(chunk) = backend_body.data().await {
+        let chunk = chunk?;
+        let len = chunk.len();
+        client_send.send_data(chunk, false)?;
+        backend_body.flow_control().release_capacity(len)?;
+    }
+    client_send.send_data(Bytes::new(), true)?;
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+//! Per-host source-IP allow/deny lists, matched by longest prefix so a broad allow (e.g.
+//! `10.0.0.0/8`) can coexist with a narrower deny (e.g. `10.1.2.0/24`).
+//!
+//! Evaluated in the connection handlers before a backend is selected. Empty/absent config means
+//! allow-all, matching current (no-ACL) behavior.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// One allow or deny rule: `network/prefix_len`.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+pub struct Rule {
+    pub network: IpAddr,
+    pub prefix_len: u8,
+}
+
+impl Rule {
+    pub fn new(network: IpAddr, prefix_len: u8) -> Self {
+        Self {
+            network,
+            prefix_len,
+        }
+    }
+}
+
+/// The allow/deny prefix sets for a host (or endpoint). A lookup finds the most specific
+/// matching prefix across *both* sets; if that most-specific match is a deny, the address is
+/// rejected, otherwise it's allowed. An address matching nothing is allowed iff `allow` is
+/// empty (i.e. there's no allow-list to be specific about).
+#[derive(Clone, Debug, Default, serde::Serialize)]
+pub struct AccessControl {
+    allow: Trie,
+    deny: Trie,
+}
+
+impl AccessControl {
+    pub fn new(allow: &[Rule], deny: &[Rule]) -> Self {
+        let mut ac = Self::default();
+        for rule in allow {
+            ac.allow.insert(rule.network, rule.prefix_len);
+        }
+        for rule in deny {
+            ac.deny.insert(rule.network, rule.prefix_len);
+        }
+        ac
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// true if `addr` is permitted.
+    pub fn allows(&self, addr: IpAddr) -> bool {
+        let allow_len = self.allow.longest_match_len(addr);
+        let deny_len = self.deny.longest_match_len(addr);
+
+        match (allow_len, deny_len) {
+            (None, None) => self.allow.is_empty(), // no allow-list at all => allow-all
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (Some(a), Some(d)) => a >= d, // more specific (or equally specific) allow wins
+        }
+    }
+}
+
+/// A tiny binary trie over address bits, one per family, so a lookup always returns the longest
+/// matching prefix's length regardless of insertion order.
+#[derive(Clone, Debug, Default)]
+struct Trie {
+    v4: Node,
+    v6: Node,
+}
+
+impl Trie {
+    fn is_empty(&self) -> bool {
+        self.v4.value.is_none() && self.v4.children == [None, None] && self.v6.value.is_none() && self.v6.children == [None, None]
+    }
+
+    fn insert(&mut self, network: IpAddr, prefix_len: u8) {
+        match network {
+            IpAddr::V4(ip) => self.v4.insert(bits32(ip), prefix_len.min(32)),
+            IpAddr::V6(ip) => self.v6.insert(bits128(ip), prefix_len.min(128)),
+        }
+    }
+
+    fn longest_match_len(&self, addr: IpAddr) -> Option<u8> {
+        match addr {
+            IpAddr::V4(ip) => self.v4.longest_match_len(bits32(ip)),
+            IpAddr::V6(ip) => self.v6.longest_match_len(bits128(ip)),
+        }
+    }
+}
+
+fn bits32(ip: Ipv4Addr) -> Vec<bool> {
+    let n = u32::from(ip);
+    (0..32).map(|i| (n >> (31 - i)) & 1 == 1).collect()
+}
+
+fn bits128(ip: Ipv6Addr) -> Vec<bool> {
+    let n = u128::from(ip);
+    (0..128).map(|i| (n >> (127 - i)) & 1 == 1).collect()
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Node {
+    /// prefix length at which a rule terminates here, if any.
+    value: Option<u8>,
+    children: [Option<Box<Node>>; 2],
+}
+
+impl Node {
+    fn insert(&mut self, bits: Vec<bool>, prefix_len: u8) {
+        let mut node = self;
+        for &bit in bits.iter().take(prefix_len as usize) {
+            node = node.children[bit as usize].get_or_insert_with(Default::default);
+        }
+        node.value = Some(prefix_len);
+    }
+
+    fn longest_match_len(&self, bits: Vec<bool>) -> Option<u8> {
+        let mut node = self;
+        let mut best = node.value;
+        for bit in bits {
+            let Some(child) = &node.children[bit as usize] else {
+                break;
+            };
+            node = child;
+            if node.value.is_some() {
+                best = node.value;
+            }
+        }
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn empty_acl_allows_everything() {
+        let ac = AccessControl::new(&[], &[]);
+        assert!(ac.is_empty());
+        assert!(ac.allows(ip("203.0.113.1")));
+    }
+
+    #[test]
+    fn allow_list_rejects_unmatched_addresses() {
+        let ac = AccessControl::new(&[Rule::new(ip("10.0.0.0"), 8)], &[]);
+        assert!(ac.allows(ip("10.1.2.3")));
+        assert!(!ac.allows(ip("192.168.1.1")));
+    }
+
+    #[test]
+    fn longest_prefix_wins_regardless_of_insertion_order() {
+        let ac = AccessControl::new(
+            &[Rule::new(ip("10.0.0.0"), 8), Rule::new(ip("10.1.2.0"), 24)],
+            &[],
+        );
+        assert!(ac.allows(ip("10.1.2.5")));
+        assert!(ac.allows(ip("10.9.9.9")));
+    }
+
+    #[test]
+    fn narrower_deny_overrides_broader_allow() {
+        let ac = AccessControl::new(
+            &[Rule::new(ip("10.0.0.0"), 8)],
+            &[Rule::new(ip("10.1.2.0"), 24)],
+        );
+        assert!(ac.allows(ip("10.9.9.9")));
+        assert!(!ac.allows(ip("10.1.2.5")));
+    }
+
+    #[test]
+    fn equally_specific_allow_and_deny_favors_allow() {
+        let ac = AccessControl::new(
+            &[Rule::new(ip("10.1.2.0"), 24)],
+            &[Rule::new(ip("10.1.2.0"), 24)],
+        );
+        assert!(ac.allows(ip("10.1.2.5")));
+    }
+
+    #[test]
+    fn deny_only_list_rejects_just_the_matched_range() {
+        let ac = AccessControl::new(&[], &[Rule::new(ip("10.1.2.0"), 24)]);
+        assert!(!ac.allows(ip("10.1.2.5")));
+        assert!(ac.allows(ip("10.9.9.9")));
+    }
+
+    #[test]
+    fn ipv6_and_ipv4_rules_are_independent() {
+        let ac = AccessControl::new(&[Rule::new(ip("2001:db8::"), 32)], &[]);
+        assert!(ac.allows(ip("2001:db8::1")));
+        assert!(!ac.allows(ip("10.0.0.1")));
+    }
+}
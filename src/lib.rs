@@ -3,27 +3,43 @@ use k8s_openapi::apimachinery::pkg::apis::meta::v1 as meta;
 use std::collections::{BTreeMap as Map, HashMap};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::watch;
 
 pub mod http1;
+pub mod http2;
+pub mod ip_acl;
+pub mod proxy_protocol;
 pub mod resolvers;
 
 pub const ALPN_H1: &[u8] = b"\x08http/1.1";
 pub const ALPN_H2: &[u8] = b"\x02h2";
 pub const ALPN_H2_H1: &[u8] = b"\x02h2\x08http/1.1";
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[derive(Clone, Debug, serde::Serialize)]
 pub struct Endpoint {
     pub namespace: String,
     pub service: String,
     pub port: PortRef,
     pub opts: EndpointOptions,
+    /// external DNS name of a `type: ExternalName` Service. When set, resolvers look this host up
+    /// directly instead of resolving `service`/`namespace` against the cluster.
+    pub external_name: Option<String>,
 }
 impl std::fmt::Display for Endpoint {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         write!(f, "{}.{}:{}", self.service, self.namespace, self.port)
     }
 }
+impl PartialEq for Endpoint {
+    fn eq(&self, other: &Self) -> bool {
+        self.namespace == other.namespace
+            && self.service == other.service
+            && self.port == other.port
+            && self.opts == other.opts
+            && self.external_name == other.external_name
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
 pub enum PortRef {
@@ -39,7 +55,7 @@ impl std::fmt::Display for PortRef {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
+#[derive(Clone, Debug, serde::Serialize)]
 pub struct EndpointOptions {
     pub secure_backends: bool,
     pub ssl_redirect: bool,
@@ -51,15 +67,108 @@ pub struct EndpointOptions {
     /// CORS allowed origins.
     /// Disables client<->endpoint direct copy as it wouldn't be consistent in keepalive cases.
     pub cors_allowed_origins: Option<Vec<String>>,
+    /// send `Access-Control-Allow-Credentials: true` when the origin matches.
+    pub cors_allow_credentials: bool,
+    /// `Access-Control-Max-Age` sent on preflight responses.
+    pub cors_max_age_secs: Option<u64>,
+    /// prepend a PROXY protocol v2 header to backend connections for this endpoint.
+    pub send_proxy_protocol: bool,
+    /// expected server name (SNI) for `secure_backends` connections. It is checked against the
+    /// backend's certificate only when `backend_ca` is set; without it, the name is sent as SNI
+    /// and no verification takes place.
+    pub backend_server_name: Option<String>,
+    /// CA bundle to verify the backend certificate chain against. `secure_backends` connections
+    /// fall back to no verification when this isn't set, so existing deployments are unaffected.
+    #[serde(skip_serializing)]
+    pub backend_ca: Option<Arc<openssl::x509::X509>>,
+    /// client key/cert pair presented to the backend for mutual TLS.
+    #[serde(skip_serializing)]
+    pub backend_client_cert: Option<Arc<CertifiedKey>>,
+    /// `max-age` for the `Strict-Transport-Security` response header, sent on TLS connections only.
+    pub hsts_max_age_secs: Option<u64>,
+    /// append `includeSubDomains` to the HSTS header. Ignored when `hsts_max_age_secs` is unset.
+    pub hsts_include_subdomains: bool,
+    /// extra headers injected into the proxied request, in order.
+    pub custom_request_headers: Vec<(String, String)>,
+    /// extra headers injected into the response before it's relayed to the client, in order.
+    pub custom_response_headers: Vec<(String, String)>,
+}
+
+impl EndpointOptions {
+    /// Returns the configured origin matching `origin`, suitable for echoing back in
+    /// `Access-Control-Allow-Origin` (never `*`, and never the whole list).
+    pub fn matching_cors_origin<'t>(&self, origin: &'t str) -> Option<&'t str> {
+        let allowed = self.cors_allowed_origins.as_ref()?;
+        allowed.iter().any(|o| o == origin).then_some(origin)
+    }
+
+    /// `Strict-Transport-Security` header value, if HSTS is configured for this endpoint.
+    pub fn hsts_header_value(&self) -> Option<String> {
+        let max_age = self.hsts_max_age_secs?;
+        Some(match self.hsts_include_subdomains {
+            true => format!("max-age={max_age}; includeSubDomains"),
+            false => format!("max-age={max_age}"),
+        })
+    }
+}
+
+impl PartialEq for EndpointOptions {
+    fn eq(&self, other: &Self) -> bool {
+        self.secure_backends == other.secure_backends
+            && self.ssl_redirect == other.ssl_redirect
+            && self.http2 == other.http2
+            && self.forwarded_header == other.forwarded_header
+            && self.cors_allowed_origins == other.cors_allowed_origins
+            && self.cors_allow_credentials == other.cors_allow_credentials
+            && self.cors_max_age_secs == other.cors_max_age_secs
+            && self.send_proxy_protocol == other.send_proxy_protocol
+            && self.backend_server_name == other.backend_server_name
+            && arc_opt_ptr_eq(&self.backend_ca, &other.backend_ca)
+            && arc_opt_ptr_eq(&self.backend_client_cert, &other.backend_client_cert)
+            && self.hsts_max_age_secs == other.hsts_max_age_secs
+            && self.hsts_include_subdomains == other.hsts_include_subdomains
+            && self.custom_request_headers == other.custom_request_headers
+            && self.custom_response_headers == other.custom_response_headers
+    }
+}
+
+/// Resolved secrets are only ever replaced wholesale on reconcile, so pointer identity is enough
+/// to tell "same cert" from "different cert" without requiring `X509`/`PKey` to implement `Eq`.
+fn arc_opt_ptr_eq<T>(a: &Option<Arc<T>>, b: &Option<Arc<T>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// Bounds for network operations that would otherwise be able to hang forever on a stalled peer.
+#[derive(Clone, Copy, Debug)]
+pub struct Timeouts {
+    /// dialing a backend's TCP connection.
+    pub connect: Duration,
+    /// the TLS handshake, client- or server-side.
+    pub tls_handshake: Duration,
+    /// reading a client's request line and headers.
+    pub header: Duration,
+    /// established proxied connections sitting idle between reads/writes.
+    pub idle: Duration,
 }
 
 pub struct Context {
     pub hosts: HostsReceiver,
     pub resolver: resolvers::cache::Cache,
+    pub timeouts: Timeouts,
+    /// cluster-wide fallback endpoint for requests matching no host at all, or whose host has
+    /// neither a matching path nor its own `default_backend`.
+    pub default_backend: Option<Endpoint>,
+    /// response compression settings applied to locally-generated bodies (error pages, redirects)
+    /// and to small buffered proxied responses; see [`http1::compression`].
+    pub compression: http1::compression::Config,
 }
 impl Context {
     pub fn host(&self, name: &str) -> Option<Arc<HostConfig>> {
-        self.hosts.borrow().get(name).cloned()
+        self.hosts.borrow().get(name)
     }
 
     pub async fn resolve(&self, ep: &Endpoint) -> Vec<SocketAddr> {
@@ -68,7 +177,65 @@ impl Context {
 }
 
 pub type HostsReceiver = watch::Receiver<Arc<Hosts>>;
-pub type Hosts = HashMap<String, Arc<HostConfig>>;
+
+/// Configured hosts, in two tiers: exact hostnames and `*.suffix` wildcards. [`Hosts::get`]
+/// implements the SNI/`Host` routing semantics (exact match, else longest matching wildcard
+/// suffix); [`Hosts::raw`] is the literal-key lookup the reconcile loop uses while accumulating
+/// rules for the same `rule.host` string.
+#[derive(Clone, Default, serde::Serialize)]
+pub struct Hosts {
+    exact: HashMap<String, Arc<HostConfig>>,
+    wildcard: HashMap<String, Arc<HostConfig>>,
+}
+
+impl Hosts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, host: String, cfg: Arc<HostConfig>) {
+        match host.strip_prefix("*.") {
+            Some(suffix) => {
+                self.wildcard.insert(suffix.to_string(), cfg);
+            }
+            None => {
+                self.exact.insert(host, cfg);
+            }
+        }
+    }
+
+    /// literal lookup by the same key passed to [`Hosts::insert`] (may itself be a `*.suffix`
+    /// wildcard host string).
+    pub fn raw(&self, host: &str) -> Option<Arc<HostConfig>> {
+        match host.strip_prefix("*.") {
+            Some(suffix) => self.wildcard.get(suffix).cloned(),
+            None => self.exact.get(host).cloned(),
+        }
+    }
+
+    /// SNI/`Host`-header routing lookup: an exact match wins, otherwise the longest `*.suffix`
+    /// wildcard whose suffix matches `host` on a label boundary.
+    pub fn get(&self, host: &str) -> Option<Arc<HostConfig>> {
+        if let Some(cfg) = self.exact.get(host) {
+            return Some(cfg.clone());
+        }
+
+        self.wildcard
+            .iter()
+            .filter(|(suffix, _)| is_wildcard_match(host, suffix))
+            .max_by_key(|(suffix, _)| suffix.len())
+            .map(|(_, cfg)| cfg.clone())
+    }
+}
+
+/// true if `host` matches the `*.suffix` wildcard, i.e. `host` ends with `.suffix` and has at
+/// least one more label before it — `*.example.com` matches `api.example.com` but not
+/// `example.com`.
+fn is_wildcard_match(host: &str, suffix: &str) -> bool {
+    host.len() > suffix.len() + 1
+        && host.ends_with(suffix)
+        && host.as_bytes()[host.len() - suffix.len() - 1] == b'.'
+}
 
 #[derive(Clone, serde::Serialize)]
 pub struct HostConfig {
@@ -79,6 +246,12 @@ pub struct HostConfig {
     #[serde(skip_serializing_if = "Map::is_empty")]
     pub prefix_matches: Map<String, Endpoint>,
     pub any_match: Option<Endpoint>,
+    /// this host's `spec.default_backend`, tried after `any_match` and distinct from it: an
+    /// `Ingress` can declare both a catch-all path rule and a top-level default backend.
+    pub default_backend: Option<Endpoint>,
+
+    #[serde(skip_serializing_if = "ip_acl::AccessControl::is_empty")]
+    pub acl: ip_acl::AccessControl,
 
     #[serde(skip_serializing)]
     pub tls_key_cert: Option<Arc<CertifiedKey>>,
@@ -91,6 +264,8 @@ impl Default for HostConfig {
             exact_matches: Map::new(),
             prefix_matches: Map::new(),
             any_match: None,
+            default_backend: None,
+            acl: ip_acl::AccessControl::default(),
             tls_key_cert: None,
         }
     }
@@ -110,6 +285,9 @@ impl HostConfig {
         any.opts.secure_backends && any.opts.http2
     }
 
+    /// Resolves in order: exact match, longest prefix match, `any_match`, this host's
+    /// `default_backend`. Callers fall back further to the cluster-wide
+    /// [`Context::default_backend`] when this also returns `None`.
     pub fn endpoint_for(&self, path: &str) -> Option<Endpoint> {
         if let Some(ep) = self.exact_matches.get(path) {
             Some(ep.clone())
@@ -121,7 +299,7 @@ impl HostConfig {
         {
             Some(ep.clone())
         } else {
-            self.any_match.clone()
+            self.any_match.clone().or_else(|| self.default_backend.clone())
         }
     }
 }
@@ -138,6 +316,41 @@ impl CertifiedKey {
             cert: X509::from_pem(crt_pem)?,
         })
     }
+
+    /// Generate a throwaway self-signed key/cert pair. Used as a TLS fallback when a client's SNI
+    /// matches no configured host, so the handshake can still complete and the HTTP layer gets a
+    /// chance to reply (e.g. with a 404) instead of the connection dying during negotiation.
+    pub fn self_signed() -> Result<Self> {
+        use openssl::{
+            asn1::Asn1Time,
+            bn::BigNum,
+            hash::MessageDigest,
+            pkey::PKey,
+            rsa::Rsa,
+            x509::{X509, X509NameBuilder},
+        };
+
+        let key = PKey::from_rsa(Rsa::generate(2048)?)?;
+
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_text("CN", "kingress")?;
+        let name = name.build();
+
+        let mut builder = X509::builder()?;
+        builder.set_version(2)?;
+        builder.set_serial_number(&BigNum::from_u32(1)?.to_asn1_integer()?)?;
+        builder.set_subject_name(&name)?;
+        builder.set_issuer_name(&name)?;
+        builder.set_pubkey(&key)?;
+        builder.set_not_before(&Asn1Time::days_from_now(0)?)?;
+        builder.set_not_after(&Asn1Time::days_from_now(3650)?)?;
+        builder.sign(&key, MessageDigest::sha256())?;
+
+        Ok(Self {
+            key,
+            cert: builder.build(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, serde::Serialize)]
@@ -0,0 +1,234 @@
+//! Minimal PROXY protocol v1/v2 support (see <https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt>).
+//!
+//! Used on the inbound side to recover the real client address behind an upstream load balancer
+//! (gated by a per-listener flag so the header can't be smuggled by untrusted clients), and on
+//! the outbound side to tell a backend the original client address on both the http1 and the
+//! native h2 backend connections.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\x00\r\nQUIT\n";
+const V1_PREFIX: &[u8] = b"PROXY ";
+
+/// Parse a PROXY protocol header from the front of `reader`, returning the decoded source
+/// address. Must be called before any other bytes (TLS, HTTP, ...) are read from the
+/// connection, and only when the listener is explicitly configured to expect it.
+pub async fn read_header<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<SocketAddr> {
+    // Read the first 2 bytes (handed on to the version-specific parser) to decide v1 (`PR`) vs v2 (starts with `\r\n`).
+    let mut prefix = [0u8; 2];
+    reader.read_exact(&mut prefix).await?;
+
+    if prefix == V1_PREFIX[..2] {
+        read_v1(reader, prefix).await
+    } else if prefix == V2_SIGNATURE[..2] {
+        read_v2(reader, prefix).await
+    } else {
+        Err(invalid("unrecognized PROXY protocol signature"))
+    }
+}
+
+async fn read_v1<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    prefix: [u8; 2],
+) -> std::io::Result<SocketAddr> {
+    let mut line = Vec::from(prefix);
+    let mut byte = [0u8; 1];
+    loop {
+        if line.len() > 107 {
+            return Err(invalid("v1 header too long"));
+        }
+        reader.read_exact(&mut byte).await?;
+        line.push(byte[0]);
+        if line.ends_with(b"\r\n") {
+            break;
+        }
+    }
+
+    let line = std::str::from_utf8(&line[..line.len() - 2]).map_err(|_| invalid("not utf8"))?;
+    let mut parts = line.split(' ');
+
+    let _proxy = parts.next().filter(|&p| p == "PROXY").ok_or_else(|| invalid("bad v1 header"))?;
+    let proto = parts.next().ok_or_else(|| invalid("missing protocol"))?;
+    if proto == "UNKNOWN" {
+        return Err(invalid("UNKNOWN proxied protocol"));
+    }
+
+    let src_ip: IpAddr = parts
+        .next()
+        .ok_or_else(|| invalid("missing source address"))?
+        .parse()
+        .map_err(|_| invalid("bad source address"))?;
+    let _dst_ip = parts.next().ok_or_else(|| invalid("missing dest address"))?;
+    let src_port: u16 = parts
+        .next()
+        .ok_or_else(|| invalid("missing source port"))?
+        .parse()
+        .map_err(|_| invalid("bad source port"))?;
+
+    Ok(SocketAddr::new(src_ip, src_port))
+}
+
+async fn read_v2<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    prefix: [u8; 2],
+) -> std::io::Result<SocketAddr> {
+    let mut rest_sig = [0u8; 10];
+    reader.read_exact(&mut rest_sig).await?;
+    if prefix != V2_SIGNATURE[..2] || rest_sig != V2_SIGNATURE[2..] {
+        return Err(invalid("bad v2 signature"));
+    }
+
+    let mut ver_cmd_fam_len = [0u8; 3];
+    reader.read_exact(&mut ver_cmd_fam_len).await?;
+    let [ver_cmd, fam, len_hi] = ver_cmd_fam_len;
+    if ver_cmd >> 4 != 2 {
+        return Err(invalid("unsupported v2 version"));
+    }
+    let command = ver_cmd & 0x0f;
+
+    let mut len_lo = [0u8; 1];
+    reader.read_exact(&mut len_lo).await?;
+    let len = u16::from_be_bytes([len_hi, len_lo[0]]) as usize;
+
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).await?;
+
+    if command != 1 {
+        // LOCAL command (health checks from the LB itself): no usable address.
+        return Err(invalid("LOCAL command carries no client address"));
+    }
+
+    match fam >> 4 {
+        // AF_INET
+        1 if body.len() >= 12 => {
+            let ip = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+            let port = u16::from_be_bytes([body[8], body[9]]);
+            Ok(SocketAddr::new(IpAddr::V4(ip), port))
+        }
+        // AF_INET6
+        2 if body.len() >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&body[0..16]);
+            let ip = Ipv6Addr::from(octets);
+            let port = u16::from_be_bytes([body[32], body[33]]);
+            Ok(SocketAddr::new(IpAddr::V6(ip), port))
+        }
+        _ => Err(invalid("unsupported address family")),
+    }
+}
+
+fn invalid(msg: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg.to_string())
+}
+
+/// Write a PROXY protocol v2 header describing `src` connecting to `dst`, for use on outbound
+/// backend connections that request it.
+pub async fn write_v2_header<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    src: SocketAddr,
+    dst: SocketAddr,
+) -> std::io::Result<()> {
+    let mut out = Vec::with_capacity(28);
+    out.extend_from_slice(V2_SIGNATURE);
+
+    match (src, dst) {
+        (SocketAddr::V4(src), SocketAddr::V4(dst)) => {
+            out.push(0x21); // version 2, command PROXY
+            out.push(0x11); // AF_INET, STREAM
+            out.extend_from_slice(&12u16.to_be_bytes());
+            out.extend_from_slice(&src.ip().octets());
+            out.extend_from_slice(&dst.ip().octets());
+            out.extend_from_slice(&src.port().to_be_bytes());
+            out.extend_from_slice(&dst.port().to_be_bytes());
+        }
+        (SocketAddr::V6(src), SocketAddr::V6(dst)) => {
+            out.push(0x21);
+            out.push(0x21); // AF_INET6, STREAM
+            out.extend_from_slice(&36u16.to_be_bytes());
+            out.extend_from_slice(&src.ip().octets());
+            out.extend_from_slice(&dst.ip().octets());
+            out.extend_from_slice(&src.port().to_be_bytes());
+            out.extend_from_slice(&dst.port().to_be_bytes());
+        }
+        _ => {
+            // mixed v4/v6 pair: fall back to the UNSPEC/unspecified family, carrying no address.
+            out.push(0x21);
+            out.push(0x00);
+            out.extend_from_slice(&0u16.to_be_bytes());
+        }
+    }
+
+    writer.write_all(&out).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[tokio::test]
+    async fn reads_v1_ipv4_header() {
+        let mut input = Cursor::new(b"PROXY TCP4 192.168.1.1 192.168.1.2 56324 443\r\n".to_vec());
+        let addr = read_header(&mut input).await.unwrap();
+        assert_eq!(addr, "192.168.1.1:56324".parse().unwrap());
+    }
+
+    #[tokio::test]
+    async fn reads_v1_ipv6_header() {
+        let mut input = Cursor::new(b"PROXY TCP6 ::1 ::1 56324 443\r\n".to_vec());
+        let addr = read_header(&mut input).await.unwrap();
+        assert_eq!(addr, "[::1]:56324".parse().unwrap());
+    }
+
+    #[tokio::test]
+    async fn rejects_v1_unknown_protocol() {
+        let mut input = Cursor::new(b"PROXY UNKNOWN\r\n".to_vec());
+        assert!(read_header(&mut input).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn writes_and_reads_back_v2_ipv4_header() {
+        let src: SocketAddr = "10.0.0.5:12345".parse().unwrap();
+        let dst: SocketAddr = "10.0.0.1:443".parse().unwrap();
+
+        let mut buf = Vec::new();
+        write_v2_header(&mut buf, src, dst).await.unwrap();
+
+        let mut input = Cursor::new(buf);
+        let addr = read_header(&mut input).await.unwrap();
+        assert_eq!(addr, src);
+    }
+
+    #[tokio::test]
+    async fn writes_and_reads_back_v2_ipv6_header() {
+        let src: SocketAddr = "[2001:db8::1]:12345".parse().unwrap();
+        let dst: SocketAddr = "[2001:db8::2]:443".parse().unwrap();
+
+        let mut buf = Vec::new();
+        write_v2_header(&mut buf, src, dst).await.unwrap();
+
+        let mut input = Cursor::new(buf);
+        let addr = read_header(&mut input).await.unwrap();
+        assert_eq!(addr, src);
+    }
+
+    #[tokio::test]
+    async fn v2_local_command_is_rejected() {
+        // version 2, command LOCAL (0x20), AF_INET/STREAM, zero-length body.
+        let mut header = Vec::new();
+        header.extend_from_slice(V2_SIGNATURE);
+        header.push(0x20);
+        header.push(0x11);
+        header.extend_from_slice(&0u16.to_be_bytes());
+
+        let mut input = Cursor::new(header);
+        assert!(read_header(&mut input).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_bad_signature() {
+        let mut input = Cursor::new(b"GET / HTTP/1.1\r\n".to_vec());
+        assert!(read_header(&mut input).await.is_err());
+    }
+}
@@ -1,32 +1,68 @@
 use eyre::Result;
 use std::net::SocketAddr;
+use std::time::Duration;
 
 use crate::Endpoint;
 
 pub mod cache;
 pub mod dns;
+pub mod health;
+pub mod hickory;
 pub mod kube;
 
 /// We need an enum of provided resolvers because we cannot use Box<dyn some-async-trait>
 pub enum Resolver {
     DnsHost {
         dns_suffix: Option<String>,
+        /// per-attempt timeout; halved when `resolve`'s `has_fallback` is set.
+        timeout: Duration,
+        /// retries after a timed-out attempt, before giving up.
+        retries: usize,
     },
     Kube {
         client: ::kube::Client,
         zone: Option<String>,
+        /// prefer EndpointSlice `hints.forZones` over hard zone-equality filtering.
+        use_topology_hints: bool,
+    },
+    Hickory {
+        resolver: ::hickory_resolver::TokioAsyncResolver,
+        dns_suffix: Option<String>,
     },
 }
 
 impl Resolver {
-    pub async fn resolve(&self, ep: &Endpoint) -> Result<Vec<SocketAddr>> {
+    /// `has_fallback` should be true when the caller already holds a usable (if stale) result to
+    /// serve on failure, e.g. `cache::Cache`'s background stale-while-revalidate refresh; only
+    /// `DnsHost` acts on it, shortening its per-attempt timeout since there's a fallback to lean
+    /// on. Other resolvers ignore it.
+    ///
+    /// The returned TTL, when known, lets `cache::Cache` size an entry's expiry off the actual DNS
+    /// record rather than the configured default; only `Hickory` currently exposes one.
+    pub async fn resolve(&self, ep: &Endpoint, has_fallback: bool) -> Result<(Vec<SocketAddr>, Option<Duration>)> {
         match self {
-            Self::DnsHost { dns_suffix } => dns::host(ep, &dns_suffix).await,
-            Self::Kube { client, zone } => {
-                kube::Resolver::new(ep, client, zone.as_ref())
+            Self::DnsHost {
+                dns_suffix,
+                timeout,
+                retries,
+            } => {
+                let timeout = if has_fallback { *timeout / 2 } else { *timeout };
+                Ok((dns::host(ep, dns_suffix, timeout, *retries).await?, None))
+            }
+            Self::Kube {
+                client,
+                zone,
+                use_topology_hints,
+            } => {
+                let addrs = kube::Resolver::new(ep, client, zone.as_ref(), *use_topology_hints)
                     .resolve()
-                    .await
+                    .await?;
+                Ok((addrs, None))
             }
+            Self::Hickory {
+                resolver,
+                dns_suffix,
+            } => hickory::resolve(resolver, ep, dns_suffix).await,
         }
     }
 }
@@ -2,57 +2,93 @@ use eyre::Result;
 use log::{debug, trace, warn};
 use std::net::SocketAddr;
 use std::num::NonZero;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 
-use super::{Endpoint, Resolver};
+use super::{health, Endpoint, Resolver};
 
 pub struct Builder {
     pub size: usize,
     pub expiry_secs: u64,
     pub negative_expiry_secs: u64,
+    /// how far past expiry an `Ok` entry may still be served while a background refresh is in
+    /// flight. 0 disables stale-while-revalidate, falling back to blocking on expiry.
+    pub stale_max_secs: u64,
+    /// bounds clamping a resolver-provided TTL (see [`ResolveResult::Ok::ttl`]) before it's used
+    /// as that entry's expiry, so a misconfigured record can't pin an endpoint forever or
+    /// re-resolve on every request.
+    pub ttl_floor_secs: u64,
+    pub ttl_ceiling_secs: u64,
     pub resolver: Resolver,
+    /// active health-check settings applied to every endpoint's resolved addresses; see
+    /// [`health::Tracker`].
+    pub health: health::Config,
 }
 impl Builder {
     pub fn build(self) -> Cache {
         Cache {
-            resolver: self.resolver,
+            resolver: Arc::new(self.resolver),
             lru: NonZero::new(self.size).map(|s| Mutex::new(lru::LruCache::new(s))),
             expiry: Duration::from_secs(self.expiry_secs),
             negative_expiry: Duration::from_secs(self.negative_expiry_secs),
+            stale_max: Duration::from_secs(self.stale_max_secs),
+            ttl_floor: Duration::from_secs(self.ttl_floor_secs),
+            ttl_ceiling: Duration::from_secs(self.ttl_ceiling_secs),
+            health: self.health,
         }
     }
 }
 
 pub struct Cache {
-    resolver: Resolver,
-    lru: Option<Mutex<lru::LruCache<String, Arc<Mutex<Option<ResolveResult>>>>>>,
+    resolver: Arc<Resolver>,
+    lru: Option<Mutex<lru::LruCache<String, Arc<CacheEntry>>>>,
     expiry: Duration,
     negative_expiry: Duration,
+    stale_max: Duration,
+    ttl_floor: Duration,
+    ttl_ceiling: Duration,
+    health: health::Config,
 }
 
 impl Cache {
     pub async fn resolve(&self, ep: &Endpoint) -> Vec<SocketAddr> {
         let Some(ref lru) = self.lru else {
+            // no cache entry to keep a `health::Tracker` in between calls, so there's nowhere to
+            // keep health state either; health checking is a cache feature.
             return self.resolve_no_cache(ep).await.result();
         };
 
         let key = ep.to_string();
 
-        let cache_entry = (lru.lock().await)
-            .get_or_insert(key, || Arc::new(Mutex::new(None)))
+        let entry = (lru.lock().await)
+            .get_or_insert(key, || Arc::new(CacheEntry::new(self.health.clone())))
             .clone();
 
-        let mut cache_entry = cache_entry.lock().await;
+        let mut guard = entry.result.lock().await;
 
-        if let Some(result) = cache_entry.as_ref() {
-            if self.is_expired(result) {
-                trace!("cached result expired: {result:?}");
-            } else {
+        if let Some(result) = guard.as_ref() {
+            let expiry = self.expiry_for(result);
+
+            if result.age() <= expiry {
                 trace!("using cached result: {result:?}");
-                return result.result();
+                let addrs = result.result();
+                drop(guard);
+                return entry.health.select(addrs).await;
+            }
+
+            if let ResolveResult::Ok { .. } = result {
+                if result.age() <= expiry + self.stale_max {
+                    trace!("serving stale result while revalidating: {result:?}");
+                    let stale = result.result();
+                    self.spawn_refresh(ep.clone(), entry.clone());
+                    drop(guard);
+                    return entry.health.select(stale).await;
+                }
             }
+
+            trace!("cached result expired: {result:?}");
         }
 
         let result = self.resolve_no_cache(ep).await;
@@ -60,27 +96,72 @@ impl Cache {
 
         // cache the result
         debug!("caching result: {ep} -> {result:?}");
-        *cache_entry = Some(result);
+        *guard = Some(result);
+        drop(guard);
 
-        ret
+        entry.health.select(ret).await
+    }
+
+    /// Re-resolve `ep` in the background and write the fresh result into `entry`, unless a
+    /// refresh is already in flight for it.
+    fn spawn_refresh(&self, ep: Endpoint, entry: Arc<CacheEntry>) {
+        if entry.refreshing.swap(true, Ordering::SeqCst) {
+            trace!("refresh already in flight for {ep}");
+            return;
+        }
+
+        let resolver = self.resolver.clone();
+
+        tokio::spawn(async move {
+            let result = resolve_with(&resolver, &ep, true).await;
+            debug!("background refresh: {ep} -> {result:?}");
+            *entry.result.lock().await = Some(result);
+            entry.refreshing.store(false, Ordering::SeqCst);
+        });
     }
 
     async fn resolve_no_cache(&self, ep: &Endpoint) -> ResolveResult {
-        let result = self.resolver.resolve(ep).await;
+        resolve_with(&self.resolver, ep, false).await
+    }
 
-        if let Err(ref e) = result {
-            warn!("failed to resolve {ep}: {e}");
+    /// The expiry to apply to `result`: the resolver-provided TTL (clamped to
+    /// `[ttl_floor, ttl_ceiling]`) if there is one, else the static `expiry`/`negative_expiry`.
+    fn expiry_for(&self, result: &ResolveResult) -> Duration {
+        match result {
+            ResolveResult::Ok { ttl, .. } => ttl
+                .map(|ttl| ttl.clamp(self.ttl_floor, self.ttl_ceiling))
+                .unwrap_or(self.expiry),
+            ResolveResult::Failed { .. } => self.negative_expiry,
         }
+    }
+}
 
-        ResolveResult::new(result)
+async fn resolve_with(resolver: &Resolver, ep: &Endpoint, has_fallback: bool) -> ResolveResult {
+    let result = resolver.resolve(ep, has_fallback).await;
+
+    if let Err(ref e) = result {
+        warn!("failed to resolve {ep}: {e}");
     }
 
-    fn is_expired(&self, result: &ResolveResult) -> bool {
-        let expiry = match result {
-            ResolveResult::Ok { .. } => self.expiry,
-            ResolveResult::Failed { .. } => self.negative_expiry,
-        };
-        result.age() > expiry
+    ResolveResult::new(result)
+}
+
+/// A cached entry plus whether a background refresh is currently running for it, so concurrent
+/// stale reads don't each spawn their own background resolve. `health` tracks the up/down state of
+/// this endpoint's resolved addresses across calls, keyed by the same cache entry as the
+/// resolution itself (see [`health::Tracker::select`]).
+struct CacheEntry {
+    result: Mutex<Option<ResolveResult>>,
+    refreshing: AtomicBool,
+    health: health::Tracker,
+}
+impl CacheEntry {
+    fn new(health: health::Config) -> Self {
+        Self {
+            result: Mutex::new(None),
+            refreshing: AtomicBool::new(false),
+            health: health::Tracker::new(health),
+        }
     }
 }
 
@@ -88,18 +169,22 @@ enum ResolveResult {
     Ok {
         cached_at: Instant,
         result: Vec<SocketAddr>,
+        /// the resolved records' TTL, when the resolver backend exposes one (currently only
+        /// `Resolver::Hickory`). `None` falls back to the cache's static `expiry`.
+        ttl: Option<Duration>,
     },
     Failed {
         cached_at: Instant,
     },
 }
 impl ResolveResult {
-    fn new(result: Result<Vec<SocketAddr>>) -> Self {
+    fn new(result: Result<(Vec<SocketAddr>, Option<Duration>)>) -> Self {
         let cached_at = Instant::now();
         match result {
-            Ok(v) => Self::Ok {
+            Ok((result, ttl)) => Self::Ok {
                 cached_at,
-                result: v,
+                result,
+                ttl,
             },
             Err(_) => Self::Failed { cached_at },
         }
@@ -127,8 +212,82 @@ impl std::fmt::Debug for ResolveResult {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         let age = self.age().as_millis();
         match self {
-            Self::Ok { result, .. } => write!(f, "Ok({age}ms ago, {result:?})"),
+            Self::Ok { result, ttl, .. } => write!(f, "Ok({age}ms ago, ttl={ttl:?}, {result:?})"),
             Self::Failed { .. } => write!(f, "Failed({age}ms ago)"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_cache() -> Cache {
+        Builder {
+            size: 64,
+            expiry_secs: 30,
+            negative_expiry_secs: 5,
+            stale_max_secs: 60,
+            ttl_floor_secs: 10,
+            ttl_ceiling_secs: 300,
+            resolver: Resolver::DnsHost {
+                dns_suffix: None,
+                timeout: Duration::from_secs(1),
+                retries: 0,
+            },
+            health: health::Config {
+                enabled: false,
+                check: health::Check::TcpConnect,
+                interval: Duration::from_secs(5),
+                timeout: Duration::from_secs(1),
+                healthy_threshold: 1,
+                unhealthy_threshold: 1,
+            },
+        }
+        .build()
+    }
+
+    fn ok_result(ttl: Option<Duration>) -> ResolveResult {
+        ResolveResult::Ok {
+            cached_at: Instant::now(),
+            result: vec![],
+            ttl,
+        }
+    }
+
+    #[test]
+    fn expiry_for_falls_back_to_static_expiry_without_ttl() {
+        let cache = test_cache();
+        assert_eq!(cache.expiry_for(&ok_result(None)), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn expiry_for_clamps_ttl_to_floor() {
+        let cache = test_cache();
+        let result = ok_result(Some(Duration::from_secs(1)));
+        assert_eq!(cache.expiry_for(&result), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn expiry_for_clamps_ttl_to_ceiling() {
+        let cache = test_cache();
+        let result = ok_result(Some(Duration::from_secs(3600)));
+        assert_eq!(cache.expiry_for(&result), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn expiry_for_uses_ttl_within_bounds() {
+        let cache = test_cache();
+        let result = ok_result(Some(Duration::from_secs(60)));
+        assert_eq!(cache.expiry_for(&result), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn expiry_for_failed_uses_negative_expiry() {
+        let cache = test_cache();
+        let failed = ResolveResult::Failed {
+            cached_at: Instant::now(),
+        };
+        assert_eq!(cache.expiry_for(&failed), Duration::from_secs(5));
+    }
+}
@@ -1,19 +1,66 @@
 use eyre::Result;
+use log::debug;
 use std::net::SocketAddr;
+use std::time::Duration;
 use tokio::net;
 
-use crate::Endpoint;
+use crate::{Endpoint, PortRef};
+
+pub async fn host(
+    ep: &Endpoint,
+    dns_suffix: &Option<String>,
+    timeout: Duration,
+    retries: usize,
+) -> Result<Vec<SocketAddr>> {
+    if let Some(name) = &ep.external_name {
+        return external_name_with_timeout(name, &ep.port, timeout, retries).await;
+    }
 
-pub async fn host(ep: &Endpoint, dns_suffix: &Option<String>) -> Result<Vec<SocketAddr>> {
     let port = &ep.port;
 
     let full_host = endpoint_dn(ep, &dns_suffix);
     let full_host = format!("{full_host}:{port}");
 
-    Ok(net::lookup_host(full_host).await?.collect())
+    lookup_host(&full_host, timeout, retries).await
+}
+
+/// Resolve a `type: ExternalName` Service straight to its external DNS name, bypassing the
+/// namespace/service-derived FQDN `endpoint_dn` would otherwise build. Used by resolvers that
+/// don't carry their own timeout/retry settings; they get the (generous) defaults below.
+pub(crate) async fn external_name(name: &str, port: &PortRef) -> Result<Vec<SocketAddr>> {
+    external_name_with_timeout(name, port, Duration::from_secs(5), 0).await
+}
+
+async fn external_name_with_timeout(
+    name: &str,
+    port: &PortRef,
+    timeout: Duration,
+    retries: usize,
+) -> Result<Vec<SocketAddr>> {
+    lookup_host(&format!("{name}:{port}"), timeout, retries).await
+}
+
+/// `tokio::net::lookup_host`, bounded by `timeout` per attempt and retried up to `retries` times
+/// on timeout before giving up, so a hung resolver can't stall the request path indefinitely.
+async fn lookup_host(host: &str, timeout: Duration, retries: usize) -> Result<Vec<SocketAddr>> {
+    let mut last_err = None;
+
+    for attempt in 0..=retries {
+        match tokio::time::timeout(timeout, net::lookup_host(host)).await {
+            Ok(Ok(addrs)) => return Ok(addrs.collect()),
+            Ok(Err(e)) => return Err(e.into()),
+            Err(_) => last_err = Some(eyre::format_err!("lookup of {host} timed out after {timeout:?}")),
+        }
+
+        if attempt < retries {
+            debug!("retrying DNS lookup of {host} (attempt {}/{retries})", attempt + 1);
+        }
+    }
+
+    Err(last_err.expect("at least one attempt is always made"))
 }
 
-fn endpoint_dn(ep: &Endpoint, suffix: &Option<String>) -> String {
+pub(crate) fn endpoint_dn(ep: &Endpoint, suffix: &Option<String>) -> String {
     let service = &ep.service;
     let namespace = &ep.namespace;
 
@@ -0,0 +1,193 @@
+//! Active health checking for the addresses [`super::cache::Cache`] resolves, so a single dead pod
+//! IP doesn't cause intermittent failures between DNS/EndpointSlice refreshes. Each address in an
+//! endpoint's resolved set is probed on its own background interval (TCP connect or an HTTP GET
+//! expecting a 2xx), and flips up/down only after `healthy_threshold`/`unhealthy_threshold`
+//! consecutive results, to damp flapping. [`Tracker`] is the per-endpoint piece of this: it's kept
+//! alongside the endpoint's `cache::CacheEntry` and reconciled against the resolved address set on
+//! every `select` call, so probes start for newly-resolved addresses and stop for ones that dropped
+//! out.
+
+use log::debug;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::http1;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub enabled: bool,
+    pub check: Check,
+    /// delay between two probes of the same address.
+    pub interval: Duration,
+    /// time a single probe is allowed to take before counting as a failure.
+    pub timeout: Duration,
+    /// consecutive successful probes needed to mark a down address up again.
+    pub healthy_threshold: u32,
+    /// consecutive failed probes needed to mark an address down.
+    pub unhealthy_threshold: u32,
+}
+
+#[derive(Debug, Clone)]
+pub enum Check {
+    /// up iff a TCP connection can be established.
+    TcpConnect,
+    /// up iff a `GET path` over the connection gets back a `2xx` status line.
+    HttpGet { path: String },
+}
+
+/// Health state for one endpoint's resolved addresses, reconciled against the current address set
+/// by [`Tracker::select`] (called from `cache::Cache::resolve` on every resolution, cached or not).
+pub struct Tracker {
+    config: Config,
+    probes: tokio::sync::Mutex<HashMap<SocketAddr, Probe>>,
+}
+
+struct Probe {
+    state: Arc<AddrState>,
+    task: tokio::task::JoinHandle<()>,
+}
+impl Drop for Probe {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct AddrState {
+    /// starts healthy: an address that has never been probed yet should be usable, not punished
+    /// for a check that hasn't run.
+    healthy: AtomicBool,
+    consecutive_successes: AtomicU32,
+    consecutive_failures: AtomicU32,
+}
+impl AddrState {
+    fn new() -> Self {
+        Self {
+            healthy: AtomicBool::new(true),
+            consecutive_successes: AtomicU32::new(0),
+            consecutive_failures: AtomicU32::new(0),
+        }
+    }
+
+    fn record(&self, ok: bool, config: &Config) {
+        if ok {
+            self.consecutive_failures.store(0, Ordering::Relaxed);
+            let successes = 1 + self.consecutive_successes.fetch_add(1, Ordering::Relaxed);
+            if successes >= config.healthy_threshold {
+                self.healthy.store(true, Ordering::Relaxed);
+            }
+        } else {
+            self.consecutive_successes.store(0, Ordering::Relaxed);
+            let failures = 1 + self.consecutive_failures.fetch_add(1, Ordering::Relaxed);
+            if failures >= config.unhealthy_threshold {
+                self.healthy.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Tracker {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            probes: tokio::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reconcile tracked addresses against the just-resolved `addrs` (start probing new ones, drop
+    /// and stop probing ones no longer resolved), then return only the addresses currently
+    /// considered healthy, falling back to the full `addrs` if every one of them is down (a flaky
+    /// health check shouldn't take an endpoint fully out of rotation).
+    pub async fn select(&self, addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        if !self.config.enabled || addrs.is_empty() {
+            return addrs;
+        }
+
+        let mut probes = self.probes.lock().await;
+
+        probes.retain(|addr, _| addrs.contains(addr));
+        for &addr in &addrs {
+            probes
+                .entry(addr)
+                .or_insert_with(|| Probe::spawn(addr, self.config.clone()));
+        }
+
+        let healthy: Vec<SocketAddr> = addrs
+            .iter()
+            .copied()
+            .filter(|addr| {
+                probes
+                    .get(addr)
+                    .is_none_or(|p| p.state.healthy.load(Ordering::Relaxed))
+            })
+            .collect();
+
+        if healthy.is_empty() {
+            debug!("all addresses unhealthy, falling back to the full resolved set: {addrs:?}");
+            addrs
+        } else {
+            healthy
+        }
+    }
+}
+
+impl Probe {
+    fn spawn(addr: SocketAddr, config: Config) -> Self {
+        let state = Arc::new(AddrState::new());
+        let task = tokio::spawn(run(addr, config, state.clone()));
+        Self { state, task }
+    }
+}
+
+async fn run(addr: SocketAddr, config: Config, state: Arc<AddrState>) {
+    loop {
+        tokio::time::sleep(config.interval).await;
+
+        let ok = match tokio::time::timeout(config.timeout, probe(addr, &config.check)).await {
+            Ok(Ok(())) => true,
+            Ok(Err(e)) => {
+                debug!("health check failed for {addr}: {e}");
+                false
+            }
+            Err(_) => {
+                debug!("health check timed out for {addr}");
+                false
+            }
+        };
+
+        state.record(ok, &config);
+    }
+}
+
+async fn probe(addr: SocketAddr, check: &Check) -> std::io::Result<()> {
+    let mut conn = TcpStream::connect(addr).await?;
+
+    let Check::HttpGet { path } = check else {
+        return Ok(());
+    };
+
+    conn.write_all(format!("GET {path} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\r\n").as_bytes())
+        .await?;
+
+    let mut conn = BufReader::new(conn);
+    let status_code = http1::Reader::new(&mut conn, Some(512))
+        .status_line(512)
+        .await
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?
+        .status_code()
+        .to_vec();
+
+    if status_code.first() == Some(&b'2') {
+        Ok(())
+    } else {
+        let status_code = String::from_utf8_lossy(&status_code);
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("non-2xx health check response: {status_code}"),
+        ))
+    }
+}
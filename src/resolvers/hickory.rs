@@ -0,0 +1,120 @@
+//! Endpoint resolution via `hickory-resolver`, for deployments that need encrypted upstream DNS
+//! (DoT/DoH) or SRV-based host+port discovery that `dns::host`'s `resolv.conf`-only lookup can't
+//! provide.
+
+use eyre::Result;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::proto::rr::Record;
+use hickory_resolver::TokioAsyncResolver;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use super::dns::endpoint_dn;
+use crate::{Endpoint, PortRef};
+
+/// Upstream transport to reach the configured nameserver(s).
+#[derive(Clone, Copy, Debug)]
+pub enum Transport {
+    /// plain UDP, falling back to TCP on truncation
+    Udp,
+    /// plain TCP
+    Tcp,
+    /// DNS-over-TLS
+    Tls,
+    /// DNS-over-HTTPS
+    Https,
+}
+
+/// Build a resolver talking to `nameservers` over `transport`. `tls_name` is the server name
+/// verified against the nameserver's certificate; required for `Tls`/`Https`, ignored otherwise.
+pub fn build(
+    nameservers: &[SocketAddr],
+    transport: Transport,
+    tls_name: Option<String>,
+) -> Result<TokioAsyncResolver> {
+    let ips: Vec<IpAddr> = nameservers.iter().map(|a| a.ip()).collect();
+    let port = nameservers.first().map_or(0, SocketAddr::port);
+
+    let group = match transport {
+        Transport::Udp => NameServerConfigGroup::from_ips_clear(&ips, port, true),
+        Transport::Tcp => NameServerConfigGroup::from_ips_tcp(&ips, port, true),
+        Transport::Tls => {
+            let name = tls_name.ok_or_else(|| eyre::format_err!("DoT requires --hickory-tls-name"))?;
+            NameServerConfigGroup::from_ips_tls(&ips, port, name, true)
+        }
+        Transport::Https => {
+            let name = tls_name.ok_or_else(|| eyre::format_err!("DoH requires --hickory-tls-name"))?;
+            NameServerConfigGroup::from_ips_https(&ips, port, name, true)
+        }
+    };
+
+    let config = ResolverConfig::from_parts(None, vec![], group);
+    Ok(TokioAsyncResolver::tokio(config, ResolverOpts::default()))
+}
+
+/// Resolve `ep` to its backend addresses, plus the lowest TTL among the records consulted (`None`
+/// if the lookup returned no records with a TTL, which shouldn't normally happen), so the caller
+/// can cache the result no longer than the DNS server itself promises.
+///
+/// Numbered ports do a plain A/AAAA lookup of the service FQDN; named ports do a SRV lookup
+/// (`_<name>._tcp.<fqdn>`), matching the records a headless service publishes for each of its
+/// ports, then resolve each SRV target's host.
+pub async fn resolve(
+    resolver: &TokioAsyncResolver,
+    ep: &Endpoint,
+    dns_suffix: &Option<String>,
+) -> Result<(Vec<SocketAddr>, Option<Duration>)> {
+    if let Some(name) = &ep.external_name {
+        let PortRef::Number(port) = &ep.port else {
+            return Err(eyre::format_err!(
+                "external name services require a numeric port"
+            ));
+        };
+        return resolve_host(resolver, name, *port).await;
+    }
+
+    let svc_dn = endpoint_dn(ep, dns_suffix);
+
+    match &ep.port {
+        PortRef::Number(port) => resolve_host(resolver, &svc_dn, *port).await,
+        PortRef::Name(name) => resolve_srv(resolver, &format!("_{name}._tcp.{svc_dn}")).await,
+    }
+}
+
+async fn resolve_host(
+    resolver: &TokioAsyncResolver,
+    host: &str,
+    port: u16,
+) -> Result<(Vec<SocketAddr>, Option<Duration>)> {
+    let ips = resolver.lookup_ip(host).await?;
+    let addrs = ips.iter().map(|ip| SocketAddr::new(ip, port)).collect();
+    Ok((addrs, min_ttl(ips.as_lookup().record_iter())))
+}
+
+async fn resolve_srv(
+    resolver: &TokioAsyncResolver,
+    name: &str,
+) -> Result<(Vec<SocketAddr>, Option<Duration>)> {
+    let srv = resolver.srv_lookup(name).await?;
+    let mut ttl = min_ttl(srv.as_lookup().record_iter());
+
+    let mut addrs = Vec::new();
+    for record in srv.iter() {
+        let target = record.target().to_utf8();
+        let ips = resolver.lookup_ip(target.trim_end_matches('.')).await?;
+        addrs.extend(ips.iter().map(|ip| SocketAddr::new(ip, record.port())));
+        ttl = min_opt(ttl, min_ttl(ips.as_lookup().record_iter()));
+    }
+    Ok((addrs, ttl))
+}
+
+fn min_ttl<'r>(records: impl Iterator<Item = &'r Record>) -> Option<Duration> {
+    records.map(|r| Duration::from_secs(r.ttl() as u64)).min()
+}
+
+fn min_opt(a: Option<Duration>, b: Option<Duration>) -> Option<Duration> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
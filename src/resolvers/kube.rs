@@ -11,10 +11,23 @@ pub struct Resolver<'t> {
     ep: &'t Endpoint,
     client: &'t Client,
     zone: Option<&'t String>,
+    /// Prefer endpoints hinted (via EndpointSlice `hints.forZones`) for `zone`, falling back to
+    /// all endpoints when none are hinted for it, instead of hard-filtering on `ep.zone`.
+    use_topology_hints: bool,
 }
 impl<'t> Resolver<'t> {
-    pub fn new(ep: &'t Endpoint, client: &'t Client, zone: Option<&'t String>) -> Self {
-        Self { ep, client, zone }
+    pub fn new(
+        ep: &'t Endpoint,
+        client: &'t Client,
+        zone: Option<&'t String>,
+        use_topology_hints: bool,
+    ) -> Self {
+        Self {
+            ep,
+            client,
+            zone,
+            use_topology_hints,
+        }
     }
 
     fn api<K>(&self) -> Api<K>
@@ -26,6 +39,10 @@ impl<'t> Resolver<'t> {
     }
 
     pub async fn resolve(self) -> Result<Vec<SocketAddr>> {
+        if let Some(name) = &self.ep.external_name {
+            return super::dns::external_name(name, &self.ep.port).await;
+        }
+
         if self.zone.is_some() {
             // a zone is provided, we must filter from endpointslices ourselves
             return self.resolve_using_endpoint_slices(None).await;
@@ -99,21 +116,63 @@ impl<'t> Resolver<'t> {
         let labels = &format!("kubernetes.io/service-name={}", &self.ep.service);
         let ep_slices = api.list(&ListParams::default().labels(labels)).await?;
 
-        return Ok((ep_slices.items.into_iter())
-            .filter_map(|slice| {
-                let port = (slice.ports?.into_iter())
-                    .filter(|p| p.name == port_name)
-                    .find_map(|p| p.port)? as u16;
+        if !self.use_topology_hints {
+            return Ok((ep_slices.items.into_iter())
+                .filter_map(|slice| {
+                    let port = (slice.ports?.into_iter())
+                        .filter(|p| p.name == port_name)
+                        .find_map(|p| p.port)? as u16;
+
+                    let iter = (slice.endpoints.into_iter())
+                        .filter(|ep| self.zone.is_none_or(|z| Some(z) == ep.zone.as_ref()))
+                        .map(|ep| ep.addresses.into_iter())
+                        .flatten()
+                        .filter_map(|addr| addr.parse::<IpAddr>().ok())
+                        .map(move |ip| SocketAddr::new(ip, port));
+                    Some(iter)
+                })
+                .flatten()
+                .collect());
+        }
 
-                let iter = (slice.endpoints.into_iter())
-                    .filter(|ep| self.zone.is_none_or(|z| Some(z) == ep.zone.as_ref()))
-                    .map(|ep| ep.addresses.into_iter())
-                    .flatten()
+        // Topology hints: partition into endpoints hinted for our zone and the rest, preferring
+        // the hinted ones but never black-holing traffic when a zone reports none.
+        let mut hinted = Vec::new();
+        let mut all = Vec::new();
+
+        for slice in ep_slices.items {
+            let Some(port) = (slice.ports.into_iter().flatten())
+                .filter(|p| p.name == port_name)
+                .find_map(|p| p.port)
+                .map(|p| p as u16)
+            else {
+                continue;
+            };
+
+            for ep in slice.endpoints {
+                let is_hinted = self.zone.is_some_and(|z| {
+                    (ep.hints.as_ref())
+                        .and_then(|h| h.for_zones.as_ref())
+                        .is_some_and(|zones| zones.iter().any(|fz| &fz.name == z))
+                });
+
+                let addrs = (ep.addresses.iter())
                     .filter_map(|addr| addr.parse::<IpAddr>().ok())
-                    .map(move |ip| SocketAddr::new(ip, port));
-                Some(iter)
-            })
-            .flatten()
-            .collect());
+                    .map(|ip| SocketAddr::new(ip, port));
+
+                if is_hinted {
+                    hinted.extend(addrs);
+                } else {
+                    all.extend(addrs);
+                }
+            }
+        }
+
+        if hinted.is_empty() {
+            all.extend(hinted);
+            Ok(all)
+        } else {
+            Ok(hinted)
+        }
     }
 }